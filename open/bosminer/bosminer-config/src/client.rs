@@ -0,0 +1,53 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Connection descriptor for a single mining protocol client, as loaded from the miner's
+//! configuration file and consumed by `bosminer::client`.
+
+use serde::Deserialize;
+
+/// Mining protocol a `Descriptor` connects with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protocol {
+    StratumV2,
+    StratumV1,
+}
+
+/// Everything needed to establish and authenticate a connection to a single pool
+#[derive(Debug, Clone, Deserialize)]
+pub struct Descriptor {
+    pub protocol: Protocol,
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    pub user: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub enable: bool,
+    /// Relative weight used by `client::Registry::recalculate_quotas` to split hashrate across
+    /// multiple pools in `Mode::LoadBalance`. Unset means "no explicit preference": the client
+    /// gets an equal share of whatever fraction the explicitly-quota'd clients leave unclaimed.
+    #[serde(default)]
+    pub quota: Option<f64>,
+}
@@ -26,8 +26,13 @@
 mod scheduler;
 
 // Sub-modules with client implementation
+pub mod stratum_v1;
 pub mod stratum_v2;
 
+// honggfuzz target for the scheduler bookkeeping below, see `fuzz::run`
+#[cfg(fuzzing)]
+pub mod fuzz;
+
 use crate::error;
 use crate::job;
 use crate::node;
@@ -44,8 +49,35 @@ use futures::lock::Mutex;
 use ii_async_compat::futures;
 
 use std::slice;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Shared cell through which a client reports the version-rolling mask it negotiated with its
+/// pool, if any. Kept as a plain side-channel attached to `Handle` (rather than a method on the
+/// `node::Client` trait) so that protocols that never negotiate the extension - `stratum_v2`
+/// included - don't need any changes at all to keep implementing the trait.
+#[derive(Debug, Default)]
+pub struct VersionRollingState {
+    mask: AtomicU32,
+    negotiated: AtomicBool,
+}
+
+impl VersionRollingState {
+    /// Called by a client once its negotiation with the pool completes successfully
+    pub fn set(&self, mask: u32) {
+        self.mask.store(mask, Ordering::Relaxed);
+        self.negotiated.store(true, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<u32> {
+        if self.negotiated.load(Ordering::Relaxed) {
+            Some(self.mask.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Handle {
@@ -55,6 +87,7 @@ pub struct Handle {
     enabled: AtomicBool,
     engine_sender: Arc<work::EngineSender>,
     solution_sender: mpsc::UnboundedSender<work::Solution>,
+    version_rolling: Arc<VersionRollingState>,
 }
 
 impl Handle {
@@ -87,6 +120,14 @@ impl Handle {
         self.node.status().status()
     }
 
+    /// Mask negotiated with the remote server via the BIP310 `mining.configure`/
+    /// `version-rolling` exchange, or `None` when the client protocol does not support the
+    /// extension or the server declined it
+    #[inline]
+    pub fn version_rolling_mask(&self) -> Option<u32> {
+        self.version_rolling.get()
+    }
+
     #[inline]
     fn start(&self) {
         if self.node.status().initiate_starting() {
@@ -153,19 +194,26 @@ impl From<Descriptor> for Handle {
         let engine_sender = Arc::new(work::EngineSender::new(None));
 
         let job_solver = job::Solver::new(engine_sender.clone(), solution_receiver);
-        let client_node = match &descriptor.protocol {
-            Protocol::StratumV2 => stratum_v2::StratumClient::new(
+        let version_rolling = Arc::new(VersionRollingState::default());
+        let client_node: Arc<dyn node::Client> = match &descriptor.protocol {
+            Protocol::StratumV2 => Arc::new(stratum_v2::StratumClient::new(
                 stratum_v2::ConnectionDetails::from_descriptor(&descriptor),
                 job_solver,
-            ),
+            )),
+            Protocol::StratumV1 => Arc::new(stratum_v1::StratumClient::new(
+                stratum_v1::ConnectionDetails::from_descriptor(&descriptor),
+                job_solver,
+                version_rolling.clone(),
+            )),
         };
 
         Self {
             descriptor,
-            node: Arc::new(client_node),
+            node: client_node,
             enabled: AtomicBool::new(false),
             engine_sender,
             solution_sender,
+            version_rolling,
         }
     }
 }
@@ -185,13 +233,179 @@ impl PartialEq for Handle {
     }
 }
 
+/// How work is distributed among the clients tracked by a `Group`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Split work among all registered clients according to their quota (pool aggregation)
+    LoadBalance,
+    /// Treat the registration order as a primary/backup chain: only one client receives work,
+    /// the rest sit idle as standbys. That is the highest-priority client that is currently
+    /// `Status::Running`, except that a recovered higher-priority client takes over only after
+    /// staying `Running` for `FAILOVER_STABILIZATION_DELAY`
+    Failover,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::LoadBalance
+    }
+}
+
+/// Delay a higher-priority client must stay `Status::Running` before `Mode::Failover` promotes
+/// it back to active, so a connection that recovers and drops again right away doesn't cause
+/// work to flap between clients
+const FAILOVER_STABILIZATION_DELAY: Duration = Duration::from_secs(30);
+
+/// Failover bookkeeping that persists across `recalculate_quotas` calls
+#[derive(Debug, Default)]
+struct FailoverState {
+    active: Option<usize>,
+    /// Higher priority client waiting out the stabilization delay before being promoted, along
+    /// with the instant it was first seen `Running`
+    candidate: Option<(usize, Instant)>,
+}
+
 #[derive(Debug)]
 pub struct Group {
     scheduler_client_handles: Mutex<Vec<scheduler::ClientHandle>>,
     midstate_count: usize,
+    mode: Mutex<Mode>,
+    failover_state: Mutex<FailoverState>,
 }
 
 impl Group {
+    /// Creates an empty client group. `midstate_count` is the fallback passed to
+    /// `work::engine::VersionRolling::new` for clients whose pool never negotiates a
+    /// version-rolling mask (see `add_client`).
+    pub fn new(midstate_count: usize) -> Self {
+        Self {
+            scheduler_client_handles: Mutex::new(vec![]),
+            midstate_count,
+            mode: Mutex::new(Mode::default()),
+            failover_state: Mutex::new(FailoverState::default()),
+        }
+    }
+
+    #[inline]
+    pub async fn mode(&self) -> Mode {
+        *self.mode.lock().await
+    }
+
+    /// Switches between `Mode::LoadBalance` and `Mode::Failover` and immediately recomputes
+    /// quotas to match
+    pub async fn set_mode(&self, mode: Mode) {
+        *self.mode.lock().await = mode;
+        *self.failover_state.lock().await = FailoverState::default();
+        self.recalculate_quotas(false).await;
+    }
+
+    /// Re-evaluates which client should be active in `Mode::Failover`. A no-op in
+    /// `Mode::LoadBalance`. Callers that want failover to actually react to a client dropping
+    /// its connection need to poll this periodically (e.g. once a second) since `Group` has no
+    /// other way of being notified of a `Handle::status()` transition.
+    pub async fn poll_failover(&self) {
+        if self.mode().await == Mode::Failover {
+            self.recalculate_quotas(false).await;
+        }
+    }
+
+    async fn recalculate_quotas(&self, reset_generated_work: bool) {
+        let mut scheduler_client_handles = self.scheduler_client_handles.lock().await;
+        if scheduler_client_handles.is_empty() {
+            return;
+        }
+
+        match self.mode().await {
+            Mode::LoadBalance => {
+                let shares = Registry::weighted_shares(
+                    scheduler_client_handles
+                        .iter()
+                        .map(|scheduler_handle| scheduler_handle.client_handle.descriptor.quota),
+                );
+                for (scheduler_handle, percentage_share) in
+                    scheduler_client_handles.iter_mut().zip(shares)
+                {
+                    if reset_generated_work {
+                        scheduler_handle.reset_generated_work();
+                    }
+                    scheduler_handle.percentage_share = percentage_share;
+                }
+            }
+            Mode::Failover => {
+                let active = self.failover_active_index(&scheduler_client_handles).await;
+                for (index, scheduler_handle) in scheduler_client_handles.iter_mut().enumerate() {
+                    if reset_generated_work {
+                        scheduler_handle.reset_generated_work();
+                    }
+                    scheduler_handle.percentage_share = if Some(index) == active { 1.0 } else { 0.0 };
+                }
+            }
+        }
+    }
+
+    /// Picks the client that should currently be active in `Mode::Failover`: the lowest index
+    /// (highest priority) client that is `Handle::is_running()`, promoted back from a lower
+    /// priority standby only after staying `Running` for `FAILOVER_STABILIZATION_DELAY`.
+    /// Falling down to a standby when the active client drops happens immediately, regardless of
+    /// whether the new best standby outranks the previous active - there is nothing to
+    /// stabilize against once the active client is gone.
+    async fn failover_active_index(
+        &self,
+        scheduler_client_handles: &[scheduler::ClientHandle],
+    ) -> Option<usize> {
+        let best_running = scheduler_client_handles
+            .iter()
+            .enumerate()
+            .filter(|(_, scheduler_handle)| scheduler_handle.client_handle.is_running())
+            .map(|(index, _)| index)
+            .min();
+
+        let mut failover_state = self.failover_state.lock().await;
+        let best_running = match best_running {
+            Some(best_running) => best_running,
+            None => {
+                *failover_state = FailoverState::default();
+                return None;
+            }
+        };
+
+        match failover_state.active {
+            Some(active) if active == best_running => {
+                failover_state.candidate = None;
+            }
+            Some(active)
+                if !scheduler_client_handles[active].client_handle.is_running() =>
+            {
+                // The active client is no longer running, regardless of whether the best
+                // standby outranks it or not; fail over right away, no need to wait it out.
+                failover_state.active = Some(best_running);
+                failover_state.candidate = None;
+            }
+            _ => {
+                // The active client is still running and `best_running` outranks it (or there
+                // is no active client yet): require it to stay up for the stabilization delay
+                // before promoting it.
+                match failover_state.candidate {
+                    Some((candidate, since)) if candidate == best_running => {
+                        if since.elapsed() >= FAILOVER_STABILIZATION_DELAY {
+                            failover_state.active = Some(best_running);
+                            failover_state.candidate = None;
+                        }
+                    }
+                    _ => failover_state.candidate = Some((best_running, Instant::now())),
+                }
+                if failover_state.active.is_none() {
+                    // Nothing active yet at all: promote immediately instead of waiting out a
+                    // stabilization delay that only makes sense once something is already up.
+                    failover_state.active = Some(best_running);
+                    failover_state.candidate = None;
+                }
+            }
+        }
+
+        failover_state.active
+    }
+
     #[inline]
     pub async fn count(&self) -> usize {
         self.scheduler_client_handles.lock().await.len()
@@ -214,17 +428,28 @@ impl Group {
 
     pub async fn add_client(&self, client_handle: Handle) -> Arc<Handle> {
         let midstate_count = self.midstate_count;
+        let client_handle = Arc::new(client_handle);
+
+        // The generator is called once per incoming job, so querying the negotiated mask here
+        // (rather than once up front) naturally picks up the result of the `mining.configure`
+        // exchange as soon as it completes, without the registration path having to wait for it.
+        let negotiating_handle = client_handle.clone();
         let _ = client_handle.replace_engine_generator(Box::new(move |job| {
-            Arc::new(work::engine::VersionRolling::new(job, midstate_count))
+            match negotiating_handle.version_rolling_mask() {
+                Some(mask) => Arc::new(work::engine::VersionRolling::new_with_mask(job, mask)),
+                None => Arc::new(work::engine::VersionRolling::new(job, midstate_count)),
+            }
         }));
         let _ = client_handle.try_disable();
 
-        let client_handle = Arc::new(client_handle);
         let scheduler_client_handle = scheduler::ClientHandle::new(client_handle.clone());
         self.scheduler_client_handles
             .lock()
             .await
             .push(scheduler_client_handle);
+        // Reset generated work so the newly added client doesn't instantly capture all future
+        // work just because it starts out with zero generated work and therefore maximal error.
+        self.recalculate_quotas(true).await;
 
         if client_handle.descriptor.enable {
             client_handle
@@ -280,7 +505,10 @@ impl Group {
     }
 }
 
-/// Keeps track of all active clients
+/// Keeps track of all active clients and their relative `percentage_share` of work, in
+/// `Mode::LoadBalance` terms (see `Group::mode` for the `Mode::Failover` counterpart, which this
+/// `Registry` does not implement - it's only used for the plain weighted-quota bookkeeping and
+/// the fuzzed register/unregister/reorder invariants)
 pub struct Registry {
     list: Vec<scheduler::Handle>,
 }
@@ -329,17 +557,18 @@ impl Registry {
     }
 
     fn recalculate_quotas(&mut self, reset_generated_work: bool) {
-        let clients = self.count();
-        let percentage_share = if clients > 0 {
-            1.0 / clients as f64
-        } else {
+        if self.is_empty() {
             return;
-        };
+        }
 
+        let shares = Self::weighted_shares(
+            self.iter()
+                .map(|scheduler_handle| scheduler_handle.client_handle.descriptor.quota),
+        );
         // Update all clients with newly calculated percentage share.
-        // Also reset generated work to prevent switching all future work to new client because
-        // new client has zero shares and so maximal error.
-        for mut scheduler_handle in self.iter_mut() {
+        // Also reset generated work to prevent switching all future work to new client
+        // because new client has zero shares and so maximal error.
+        for (mut scheduler_handle, percentage_share) in self.iter_mut().zip(shares) {
             if reset_generated_work {
                 scheduler_handle.reset_generated_work();
             }
@@ -347,6 +576,48 @@ impl Registry {
         }
     }
 
+    /// Turns each client's optional `descriptor.quota` weight into a `percentage_share`.
+    ///
+    /// When every client specifies a quota, shares are the classic weighted average
+    /// `weight_i / sum(weights)`, so the units of `quota` don't matter as long as they are
+    /// consistent across clients. When some clients leave `quota` unset, the specified weights
+    /// are instead treated as direct fractions of the whole and the unspecified clients split
+    /// whatever fraction is left over equally, so e.g. two pools quota'd at `0.7` and `0.3`
+    /// split hashrate 70/30 while a third, unquota'd pool added later gets nothing until the
+    /// others are adjusted. The returned shares always sum to ~1.0.
+    fn weighted_shares(quotas: impl Iterator<Item = Option<f64>>) -> Vec<f64> {
+        let quotas: Vec<Option<f64>> = quotas.collect();
+        let specified_sum: f64 = quotas.iter().filter_map(|quota| *quota).sum();
+        let unspecified_count = quotas.iter().filter(|quota| quota.is_none()).count();
+
+        if unspecified_count == 0 {
+            return if specified_sum > 0.0 {
+                quotas
+                    .iter()
+                    .map(|quota| quota.unwrap_or(0.0) / specified_sum)
+                    .collect()
+            } else {
+                // Degenerate case: every client explicitly asked for a zero share. Fall back to
+                // an equal split rather than handing out all-zero quotas.
+                vec![1.0 / quotas.len() as f64; quotas.len()]
+            };
+        }
+
+        if specified_sum >= 1.0 {
+            // Over-subscribed: normalize the specified weights and leave nothing for the rest.
+            return quotas
+                .iter()
+                .map(|quota| quota.map(|weight| weight / specified_sum).unwrap_or(0.0))
+                .collect();
+        }
+
+        let remaining_share = (1.0 - specified_sum) / unspecified_count as f64;
+        quotas
+            .iter()
+            .map(|quota| quota.unwrap_or(remaining_share))
+            .collect()
+    }
+
     /// Register client that implements a protocol set in `descriptor`
     fn register_client(&mut self, client_handle: Arc<Handle>) -> &scheduler::Handle {
         self.list.push(scheduler::Handle::new(client_handle));
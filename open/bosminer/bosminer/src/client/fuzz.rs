@@ -0,0 +1,224 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! honggfuzz target for the pure scheduler bookkeeping: `Registry::register_client` /
+//! `unregister_client` / `reorder_clients` and `Group::move_client_to`, run against randomized
+//! sequences of operations instead of the fixed-sleep, real-hardware flow that
+//! `test_work_generation` drives.
+//!
+//! This lives as a `client` submodule (rather than a separate fuzz crate) specifically so it can
+//! call `Registry`'s scheduling methods directly without widening their visibility beyond what
+//! the rest of the crate needs. Only compiled with `--cfg fuzzing`, driven by the thin
+//! `hfuzz_targets/scheduler.rs` binary.
+//!
+//! Run with: `HFUZZ_BUILD_ARGS="--cfg fuzzing" cargo hfuzz run scheduler`
+#![cfg(fuzzing)]
+
+use super::{Descriptor, Group, Handle, Protocol, Registry};
+
+use arbitrary::{Arbitrary, Unstructured};
+use honggfuzz::fuzz;
+use ii_async_compat::futures;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Arbitrary)]
+struct FuzzDescriptor {
+    protocol_is_v1: bool,
+    host: String,
+    port: Option<u16>,
+    user: String,
+    password: Option<String>,
+    enable: bool,
+    /// Scaled down to `0.0..=1.0` when converted to `Descriptor::quota`; `None` means "no
+    /// explicit quota"
+    quota: Option<u8>,
+}
+
+impl From<FuzzDescriptor> for Descriptor {
+    fn from(fuzzed: FuzzDescriptor) -> Self {
+        Descriptor {
+            protocol: if fuzzed.protocol_is_v1 {
+                Protocol::StratumV1
+            } else {
+                Protocol::StratumV2
+            },
+            host: fuzzed.host,
+            port: fuzzed.port,
+            user: fuzzed.user,
+            password: fuzzed.password,
+            enable: fuzzed.enable,
+            quota: fuzzed.quota.map(|quota| quota as f64 / u8::MAX as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Arbitrary)]
+enum Op {
+    Unregister(u8),
+    /// Swap sequence applied to the current handle list to build a permutation, since an
+    /// arbitrary `Vec<Arc<Handle>>` can't be generated directly
+    Reorder(Vec<u8>),
+    MoveClient(u8, u8),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    descriptors: Vec<FuzzDescriptor>,
+    ops: Vec<Op>,
+}
+
+/// Checks that the registry's externally visible client list is consistent with how many
+/// handles we expect it to hold: no client silently dropped or duplicated by a register /
+/// unregister / reorder call.
+fn assert_registry_invariants(registry: &Registry, expected_len: usize) {
+    let clients = registry.get_clients();
+    assert_eq!(
+        clients.len(),
+        expected_len,
+        "BUG: registry lost or gained a client handle"
+    );
+    assert_eq!(clients.len(), registry.count());
+
+    let mut seen = HashSet::new();
+    for client in &clients {
+        assert!(
+            seen.insert(Arc::as_ptr(client) as usize),
+            "BUG: duplicate client handle in registry"
+        );
+    }
+}
+
+fn run_once(data: &[u8]) {
+    let mut unstructured = Unstructured::new(data);
+    let input = match Input::arbitrary(&mut unstructured) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+    if input.descriptors.is_empty() {
+        return;
+    }
+
+    let mut registry = Registry::new();
+    let mut handles: Vec<Arc<Handle>> = Vec::new();
+
+    for fuzzed in &input.descriptors {
+        let handle = Arc::new(Handle::from(Descriptor::from(fuzzed.clone())));
+        registry.register_client(handle.clone());
+        handles.push(handle);
+        assert_registry_invariants(&registry, handles.len());
+    }
+
+    // The same pointer-identity comparison `Handle::matching_solution` relies on (see the NOTE
+    // on that method): a handle always matches itself and never matches an unrelated one,
+    // regardless of how many other clients were registered around it.
+    for (i, handle_i) in handles.iter().enumerate() {
+        // Compare against a fresh `Arc` clone rather than `handle_i` itself, so this actually
+        // exercises `PartialEq`'s pointer-identity logic instead of tripping `clippy::eq_op` on
+        // a comparison that's trivially true by reflexivity.
+        let handle_i_clone = handle_i.clone();
+        assert_eq!(handle_i, &handle_i_clone, "BUG: a handle must match itself");
+        for handle_j in handles.iter().skip(i + 1) {
+            assert_ne!(
+                handle_i, handle_j,
+                "BUG: distinct handles must not compare equal"
+            );
+        }
+    }
+
+    for op in input.ops {
+        if handles.is_empty() {
+            break;
+        }
+        match op {
+            Op::Unregister(index) => {
+                let index = index as usize % handles.len();
+                let handle = handles.remove(index);
+                let _ = registry.unregister_client(handle);
+                assert_registry_invariants(&registry, handles.len());
+            }
+            Op::Reorder(swaps) => {
+                let mut reordered = handles.clone();
+                for (i, byte) in swaps.iter().enumerate() {
+                    let j = *byte as usize % reordered.len();
+                    reordered.swap(i % reordered.len(), j);
+                }
+                if registry.reorder_clients(reordered.iter()).is_ok() {
+                    handles = reordered;
+                    assert_registry_invariants(&registry, handles.len());
+                }
+            }
+            Op::MoveClient(from, to) => {
+                // `Group` keeps its own, independent client list, freshly built from the
+                // original descriptors each time (rather than from `handles`, which `Op::
+                // Unregister`/`Op::Reorder` may have already shuffled) so that
+                // `move_client_to`'s index-juggling slice concatenation gets fuzzed in
+                // isolation from the registry above.
+                futures::executor::block_on(async {
+                    let group = Group::new(1);
+                    for fuzzed in &input.descriptors {
+                        // `Group::add_client` honors `descriptor.enable` by starting the node,
+                        // which for a real protocol client means connecting out to whatever
+                        // host/port the fuzzer produced. This harness only exercises the pure
+                        // index bookkeeping in `move_client_to`, so keep every client disabled
+                        // regardless of what the fuzzer generated.
+                        let mut descriptor = Descriptor::from(fuzzed.clone());
+                        descriptor.enable = false;
+                        group.add_client(Handle::from(descriptor)).await;
+                    }
+
+                    let len = group.count().await;
+                    if len == 0 {
+                        return;
+                    }
+                    let from = from as usize % len;
+                    let to = to as usize % len;
+                    if group.move_client_to(from, to).await.is_ok() {
+                        let after = group.get_clients().await;
+                        assert_eq!(
+                            after.len(),
+                            len,
+                            "BUG: move_client_to dropped or duplicated a handle"
+                        );
+                        let mut seen = HashSet::new();
+                        for client in &after {
+                            assert!(
+                                seen.insert(Arc::as_ptr(client) as usize),
+                                "BUG: move_client_to produced a duplicate handle"
+                            );
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Entry point called by the `hfuzz_targets/scheduler` binary
+pub fn run() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            run_once(data);
+        });
+    }
+}
@@ -0,0 +1,570 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Stratum V1 client. Speaks the original line-delimited JSON-RPC mining protocol
+//! (`mining.subscribe` / `mining.authorize` / `mining.set_difficulty` / `mining.notify` /
+//! `mining.submit`) so that bosminer can connect directly to pools that have not adopted
+//! Stratum V2 yet.
+
+mod rpc;
+
+use crate::error;
+use crate::job;
+use crate::node;
+use crate::stats;
+use crate::work;
+
+use bosminer_config::client::Descriptor;
+
+use crate::client::VersionRollingState;
+
+use ii_async_compat::{futures, prelude::*, tokio};
+use ii_logging::macros::*;
+
+use futures::channel::mpsc;
+use futures::lock::Mutex;
+use futures::stream::StreamExt;
+
+use serde_json::Value;
+
+use sha2::{Digest, Sha256};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default port used when the descriptor does not specify one
+const DEFAULT_PORT: u16 = 3333;
+
+/// Initial delay before a dropped connection is retried, doubled on every consecutive failure
+/// up to `RECONNECT_MAX_DELAY` instead of hammering a pool that is down
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Upper bound for the exponential reconnect backoff
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(120);
+
+/// A connection that stayed up for at least this long is considered healthy again, resetting
+/// the backoff back to `RECONNECT_INITIAL_DELAY` instead of letting it creep towards the max
+/// forever because of unrelated, occasional drops
+const RECONNECT_HEALTHY_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// User agent reported to the pool during `mining.subscribe`
+const USER_AGENT: &str = concat!("bosminer/", env!("CARGO_PKG_VERSION"));
+
+/// Rolling mask we propose to the pool during the `mining.configure`/`version-rolling`
+/// exchange (BIP310). The device only needs a handful of bits to cover its midstate count, but
+/// we ask for the full ASIC Boost range and let the pool narrow it down.
+const VERSION_ROLLING_MASK: u32 = 0x1fff_e000;
+
+/// Minimum number of rollable bits we require to bother with version rolling at all
+const VERSION_ROLLING_MIN_BIT_COUNT: u32 = 2;
+
+/// Connection parameters extracted from the generic client `Descriptor`
+#[derive(Debug, Clone)]
+pub struct ConnectionDetails {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+}
+
+impl ConnectionDetails {
+    pub fn from_descriptor(descriptor: &Descriptor) -> Self {
+        Self {
+            host: descriptor.host.clone(),
+            port: descriptor.port.unwrap_or(DEFAULT_PORT),
+            user: descriptor.user.clone(),
+            password: descriptor.password.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Extranonce parameters handed out by the pool in its `mining.subscribe` response. Needed to
+/// assemble the real coinbase (and therefore the real merkle root) for every job, and to echo
+/// `extranonce2` back in `mining.submit`.
+struct Subscription {
+    extranonce1: Vec<u8>,
+    extranonce2_size: usize,
+}
+
+impl Subscription {
+    fn from_response(response: &rpc::Response) -> Result<Self, error::Client> {
+        let result = response
+            .result
+            .as_ref()
+            .and_then(Value::as_array)
+            .ok_or(error::Client::Protocol)?;
+        let extranonce1 = result
+            .get(1)
+            .and_then(Value::as_str)
+            .ok_or(error::Client::Protocol)?;
+        let extranonce2_size = result
+            .get(2)
+            .and_then(Value::as_u64)
+            .ok_or(error::Client::Protocol)?;
+
+        Ok(Self {
+            extranonce1: hex::decode(extranonce1).map_err(|_| error::Client::Protocol)?,
+            extranonce2_size: extranonce2_size as usize,
+        })
+    }
+}
+
+/// A V1 job as announced by `mining.notify`, adapted to the generic `job::Bitcoin` interface
+/// used by the rest of bosminer (block header construction, difficulty target, ...). All fields
+/// are already decoded/assembled into the binary form `job::Bitcoin` expects, so nothing here
+/// touches the wire format again.
+#[derive(Debug, Clone)]
+struct StratumJob {
+    job_id: String,
+    version: u32,
+    previous_hash: Vec<u8>,
+    merkle_root: [u8; 32],
+    time: u32,
+    bits: u32,
+    difficulty: f64,
+    /// `extranonce2` used to build this job's coinbase, echoed back in `mining.submit`
+    extranonce2: Vec<u8>,
+}
+
+impl StratumJob {
+    /// Decodes a `mining.notify` message into a `StratumJob`, assembling the real coinbase
+    /// (`coinbase1 || extranonce1 || extranonce2 || coinbase2`) and folding it through the
+    /// merkle branches to produce the real merkle root, rather than a placeholder.
+    ///
+    /// `extranonce2` is fixed at all-zeroes: the device's nonce range combined with the
+    /// negotiated version-rolling mask already gives each job ample search space, so rolling
+    /// `extranonce2` per job isn't needed here (pools that require it for multi-worker
+    /// deduplication are out of scope for this client).
+    fn from_notify(
+        notify: &rpc::NotifyJob,
+        subscription: &Subscription,
+        difficulty: f64,
+    ) -> Result<Self, error::Client> {
+        let version =
+            u32::from_str_radix(&notify.version, 16).map_err(|_| error::Client::Protocol)?;
+        let bits = u32::from_str_radix(&notify.bits, 16).map_err(|_| error::Client::Protocol)?;
+        let time = u32::from_str_radix(&notify.time, 16).map_err(|_| error::Client::Protocol)?;
+
+        // Stratum sends prev_hash as a sequence of 32-bit little-endian words in reverse order;
+        // swap the word order back to get the natural block-header byte order.
+        let previous_hash = swap_word_order(
+            &hex::decode(&notify.prev_hash).map_err(|_| error::Client::Protocol)?,
+        );
+
+        let extranonce2 = vec![0u8; subscription.extranonce2_size];
+
+        let mut coinbase =
+            hex::decode(&notify.coin_base_1).map_err(|_| error::Client::Protocol)?;
+        coinbase.extend_from_slice(&subscription.extranonce1);
+        coinbase.extend_from_slice(&extranonce2);
+        coinbase
+            .extend_from_slice(&hex::decode(&notify.coin_base_2).map_err(|_| error::Client::Protocol)?);
+
+        let mut merkle_root = sha256d(&coinbase);
+        for branch in &notify.merkle_branches {
+            let branch = hex::decode(branch).map_err(|_| error::Client::Protocol)?;
+            let mut data = Vec::with_capacity(merkle_root.len() + branch.len());
+            data.extend_from_slice(&merkle_root);
+            data.extend_from_slice(&branch);
+            merkle_root = sha256d(&data);
+        }
+
+        Ok(Self {
+            job_id: notify.job_id.clone(),
+            version,
+            previous_hash,
+            merkle_root,
+            time,
+            bits,
+            difficulty,
+            extranonce2,
+        })
+    }
+}
+
+impl job::Bitcoin for StratumJob {
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn previous_hash(&self) -> &[u8] {
+        &self.previous_hash
+    }
+
+    fn merkle_root(&self) -> &[u8] {
+        &self.merkle_root
+    }
+
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn difficulty(&self) -> f64 {
+        self.difficulty
+    }
+
+    fn is_valid(&self) -> bool {
+        true
+    }
+}
+
+/// Reverses the order of 4-byte words in `bytes`, leaving the bytes within each word untouched
+fn swap_word_order(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len());
+    for word in bytes.chunks(4).rev() {
+        result.extend_from_slice(word);
+    }
+    result
+}
+
+/// Double SHA-256, as used throughout the Bitcoin block header and merkle tree
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    result.copy_from_slice(&Sha256::digest(&Sha256::digest(data)));
+    result
+}
+
+/// Stratum V1 protocol client
+///
+/// Owns the same `job::Solver` plumbing as `stratum_v2::StratumClient`: a background task
+/// maintains the TCP connection and the subscribe/authorize/notify/submit handshake, converts
+/// `mining.notify` into `job::Bitcoin` for the scheduler and `work::Solution`s coming back from
+/// hardware into `mining.submit`.
+#[derive(Debug)]
+pub struct StratumClient {
+    connection_details: ConnectionDetails,
+    status: crate::sync::StatusMonitor,
+    job_solver: Mutex<Option<job::Solver>>,
+    next_request_id: AtomicU32,
+    client_stats: stats::BasicClient,
+    /// Shared with this client's `Handle` so the negotiated mask can be read by
+    /// `Handle::version_rolling_mask` without going through `node::Client`
+    version_rolling: Arc<VersionRollingState>,
+    /// Signalled by `stop()` so a `connect_and_serve` call blocked on the socket notices right
+    /// away instead of only giving up once the next line (or solution) arrives.
+    stop_notify: Notify,
+}
+
+impl StratumClient {
+    pub fn new(
+        connection_details: ConnectionDetails,
+        job_solver: job::Solver,
+        version_rolling: Arc<VersionRollingState>,
+    ) -> Self {
+        Self {
+            connection_details,
+            status: Default::default(),
+            job_solver: Mutex::new(Some(job_solver)),
+            next_request_id: AtomicU32::new(0),
+            client_stats: Default::default(),
+            version_rolling,
+            stop_notify: Notify::new(),
+        }
+    }
+
+    fn next_id(&self) -> u32 {
+        self.next_request_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Runs the connect -> subscribe -> authorize -> notify loop until the client is stopped,
+    /// reconnecting with exponential backoff whenever the TCP connection drops so a pool that
+    /// is down does not get hammered, instead of leaving the client dead after one failure.
+    async fn run(self: Arc<Self>) {
+        let (engine_sender, mut solution_receiver) = {
+            let mut job_solver = self.job_solver.lock().await;
+            let job_solver = job_solver.take().expect("BUG: missing job solver");
+            job_solver.split()
+        };
+
+        let mut reconnect_delay = RECONNECT_INITIAL_DELAY;
+        while self.status.status() == crate::sync::Status::Running {
+            let attempt_started = std::time::Instant::now();
+            match self
+                .clone()
+                .connect_and_serve(&engine_sender, &mut solution_receiver)
+                .await
+            {
+                Ok(()) => info!("stratum-v1: connection to pool closed"),
+                Err(e) => warn!("stratum-v1: connection error: {}", e),
+            }
+            if self.status.status() != crate::sync::Status::Running {
+                break;
+            }
+
+            let delay = if attempt_started.elapsed() >= RECONNECT_HEALTHY_THRESHOLD {
+                RECONNECT_INITIAL_DELAY
+            } else {
+                reconnect_delay
+            };
+            warn!("stratum-v1: reconnecting to pool in {:?}", delay);
+            ii_async_compat::sleep(delay).await;
+            reconnect_delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+        // The client has been stopped: keep discarding solutions that still arrive until the
+        // sending side of the channel is dropped.
+        while solution_receiver.next().await.is_some() {}
+    }
+
+    async fn connect_and_serve(
+        self: Arc<Self>,
+        engine_sender: &work::EngineSender,
+        solution_receiver: &mut mpsc::UnboundedReceiver<work::Solution>,
+    ) -> Result<(), error::Client> {
+        let stream = TcpStream::connect((
+            self.connection_details.host.as_str(),
+            self.connection_details.port,
+        ))
+        .await
+        .map_err(error::Client::Connection)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let configure_id = self.next_id();
+        self.send(
+            &mut writer,
+            &rpc::Request::configure(
+                configure_id,
+                VERSION_ROLLING_MASK,
+                VERSION_ROLLING_MIN_BIT_COUNT,
+            ),
+        )
+        .await?;
+        let subscribe_id = self.next_id();
+        self.send(
+            &mut writer,
+            &rpc::Request::subscribe(subscribe_id, USER_AGENT),
+        )
+        .await?;
+        self.send(
+            &mut writer,
+            &rpc::Request::authorize(
+                self.next_id(),
+                &self.connection_details.user,
+                &self.connection_details.password,
+            ),
+        )
+        .await?;
+
+        let mut difficulty = 1.0f64;
+        let mut subscription: Option<Subscription> = None;
+        let mut active_job: Option<StratumJob> = None;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line.map_err(error::Client::Connection)? {
+                        Some(line) => line,
+                        None => return Ok(()), // pool closed the connection
+                    };
+                    let message: Value = match serde_json::from_str(&line) {
+                        Ok(message) => message,
+                        Err(_) => {
+                            warn!("stratum-v1: ignoring malformed line: {}", line);
+                            continue;
+                        }
+                    };
+
+                    if let Ok(notification) = serde_json::from_value::<rpc::Notification>(message.clone()) {
+                        let params = &notification.params;
+                        match notification.method.as_str() {
+                            "mining.notify" => {
+                                let notify = rpc::NotifyJob::from_params(params);
+                                match (notify, subscription.as_ref()) {
+                                    (Some(notify), Some(subscription)) => {
+                                        match StratumJob::from_notify(&notify, subscription, difficulty) {
+                                            Ok(job) => {
+                                                trace!(
+                                                    "stratum-v1: new job {} (clean_jobs={})",
+                                                    job.job_id, notify.clean_jobs
+                                                );
+                                                active_job = Some(job.clone());
+                                                let bitcoin_job: Arc<dyn job::Bitcoin> = Arc::new(job);
+                                                // `Group::add_client` already installed a generator that
+                                                // queries `self.version_rolling` on every call, so it picks
+                                                // up the negotiated mask (and the real midstate count) on
+                                                // its own; just hand it the freshly decoded job instead of
+                                                // also replacing the generator here with one that hardcodes
+                                                // a midstate count of 1.
+                                                engine_sender.send_job(bitcoin_job);
+                                            }
+                                            Err(e) => warn!("stratum-v1: dropping malformed job: {}", e),
+                                        }
+                                    }
+                                    _ => warn!(
+                                        "stratum-v1: ignoring mining.notify received before mining.subscribe completed"
+                                    ),
+                                }
+                            }
+                            "mining.set_difficulty" => {
+                                if let Some(new_difficulty) = params.get(0).and_then(Value::as_f64) {
+                                    difficulty = new_difficulty;
+                                }
+                            }
+                            method => trace!("stratum-v1: ignoring notification {}", method),
+                        }
+                    } else if let Ok(response) = serde_json::from_value::<rpc::Response>(message) {
+                        if response.id == configure_id {
+                            self.handle_configure_response(&response);
+                        } else if response.id == subscribe_id {
+                            match Subscription::from_response(&response) {
+                                Ok(parsed) => subscription = Some(parsed),
+                                Err(e) => warn!("stratum-v1: malformed mining.subscribe response: {}", e),
+                            }
+                        } else if response.is_ok() {
+                            trace!("stratum-v1: pool acknowledged request {}", response.id);
+                        } else {
+                            warn!(
+                                "stratum-v1: pool rejected request {}: {:?}",
+                                response.id, response.error
+                            );
+                        }
+                    }
+                }
+                solution = solution_receiver.next() => {
+                    match solution {
+                        Some(solution) => self.submit_solution(&mut writer, &solution, &active_job).await?,
+                        None => return Ok(()), // job solver was dropped, client is shutting down
+                    }
+                }
+                _ = self.stop_notify.notified() => {
+                    info!("stratum-v1: stop requested, closing connection to pool");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Parses the result of `mining.configure` and stores the accepted version-rolling mask, if
+    /// the pool granted the extension, on the shared `VersionRollingState`. Leaving it unset
+    /// makes `version_rolling.get()` report `None`, so callers fall back to the fixed
+    /// midstate-count behavior.
+    fn handle_configure_response(&self, response: &rpc::Response) {
+        let accepted = response
+            .result
+            .as_ref()
+            .and_then(|result| result.get("version-rolling"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let mask = response
+            .result
+            .as_ref()
+            .and_then(|result| result.get("version-rolling.mask"))
+            .and_then(Value::as_str)
+            .and_then(|mask| u32::from_str_radix(mask, 16).ok());
+
+        match (accepted, mask) {
+            (true, Some(mask)) => self.version_rolling.set(mask),
+            _ => warn!("stratum-v1: pool declined version-rolling, falling back to midstate_count"),
+        }
+    }
+
+    /// Submits a hardware solution against the most recently announced job of this connection.
+    /// `work::Solution` carries no job reference, so a solution found for an earlier job is
+    /// submitted under the current job's id. A solution that arrives before any job has been
+    /// announced on this connection is dropped.
+    async fn submit_solution(
+        &self,
+        writer: &mut tokio::net::tcp::OwnedWriteHalf,
+        solution: &work::Solution,
+        active_job: &Option<StratumJob>,
+    ) -> Result<(), error::Client> {
+        let active_job = match active_job {
+            Some(active_job) => active_job,
+            None => {
+                warn!("stratum-v1: dropping solution with no active job to submit against");
+                return Ok(());
+            }
+        };
+
+        // BIP310: only echo rolled version bits back to the pool when version-rolling was
+        // actually negotiated, restricted to the mask the pool accepted - a solution found
+        // before negotiation completed (or against a declining pool) has nothing to roll.
+        let version_bits = self
+            .version_rolling
+            .get()
+            .map(|mask| format!("{:08x}", solution.version_bits() & mask));
+
+        let request = rpc::Request::submit(
+            self.next_id(),
+            &self.connection_details.user,
+            &active_job.job_id,
+            &hex::encode(&active_job.extranonce2),
+            &format!("{:08x}", active_job.time),
+            &format!("{:08x}", solution.nonce()),
+            version_bits.as_deref(),
+        );
+        self.send(writer, &request).await
+    }
+
+    async fn send(
+        &self,
+        writer: &mut tokio::net::tcp::OwnedWriteHalf,
+        request: &rpc::Request,
+    ) -> Result<(), error::Client> {
+        let mut line = serde_json::to_string(request).expect("BUG: request serialization failed");
+        line.push('\n');
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(error::Client::Connection)
+    }
+}
+
+#[async_trait::async_trait]
+impl node::Client for StratumClient {
+    fn start(self: Arc<Self>) {
+        ii_async_compat::spawn(self.run());
+    }
+
+    fn stop(&self) {
+        // `run`'s reconnect loop rechecks `status()` on every iteration, but a `connect_and_serve`
+        // call blocked on the socket wouldn't otherwise notice a stop request until the next line
+        // or solution arrived (or never, against a pool that has gone quiet). Wake it immediately
+        // so the TCP connection is actually dropped instead of lingering.
+        self.stop_notify.notify_one();
+    }
+
+    #[inline]
+    fn status(&self) -> &crate::sync::StatusMonitor {
+        &self.status
+    }
+
+    #[inline]
+    fn client_stats(&self) -> &dyn stats::Client {
+        &self.client_stats
+    }
+
+    async fn get_last_job(&self) -> Option<Arc<dyn job::Bitcoin>> {
+        None
+    }
+}
+
+impl node::Info for StratumClient {}
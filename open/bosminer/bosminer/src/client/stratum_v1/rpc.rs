@@ -0,0 +1,161 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Minimal set of Stratum V1 JSON-RPC messages needed to keep a mining session alive:
+//! `mining.configure`, `mining.subscribe`, `mining.authorize`, `mining.set_difficulty`,
+//! `mining.notify` and `mining.submit`. Each line on the wire is one JSON object terminated
+//! with `\n`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Request sent by the client. `id` is echoed back by the server in the matching response.
+#[derive(Debug, Clone, Serialize)]
+pub struct Request {
+    pub id: u32,
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
+impl Request {
+    /// BIP310 `mining.configure` proposing the `version-rolling` extension with the given
+    /// rolling `mask` and the minimum number of rollable bits required
+    pub fn configure(id: u32, mask: u32, min_bit_count: u32) -> Self {
+        Self {
+            id,
+            method: "mining.configure".to_string(),
+            params: vec![
+                serde_json::json!(["version-rolling"]),
+                serde_json::json!({
+                    "version-rolling.mask": format!("{:08x}", mask),
+                    "version-rolling.min-bit-count": min_bit_count,
+                }),
+            ],
+        }
+    }
+
+    pub fn subscribe(id: u32, user_agent: &str) -> Self {
+        Self {
+            id,
+            method: "mining.subscribe".to_string(),
+            params: vec![Value::String(user_agent.to_string())],
+        }
+    }
+
+    pub fn authorize(id: u32, user: &str, password: &str) -> Self {
+        Self {
+            id,
+            method: "mining.authorize".to_string(),
+            params: vec![
+                Value::String(user.to_string()),
+                Value::String(password.to_string()),
+            ],
+        }
+    }
+
+    /// `version_bits` is the 6th, optional BIP310 parameter: the hex-encoded version bits the
+    /// hardware actually rolled while finding this solution, already restricted to the mask the
+    /// pool accepted during `mining.configure`. Omitted entirely when version-rolling was never
+    /// negotiated, since pools that never advertised the extension don't expect the extra param.
+    pub fn submit(
+        id: u32,
+        user: &str,
+        job_id: &str,
+        extra_nonce2: &str,
+        ntime: &str,
+        nonce: &str,
+        version_bits: Option<&str>,
+    ) -> Self {
+        let mut params = vec![
+            Value::String(user.to_string()),
+            Value::String(job_id.to_string()),
+            Value::String(extra_nonce2.to_string()),
+            Value::String(ntime.to_string()),
+            Value::String(nonce.to_string()),
+        ];
+        if let Some(version_bits) = version_bits {
+            params.push(Value::String(version_bits.to_string()));
+        }
+        Self {
+            id,
+            method: "mining.submit".to_string(),
+            params,
+        }
+    }
+}
+
+/// Response to a request we have sent, matched to it via `id`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Response {
+    pub id: u32,
+    pub result: Option<Value>,
+    pub error: Option<Value>,
+}
+
+impl Response {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.result != Some(Value::Bool(false))
+    }
+}
+
+/// Unsolicited message pushed by the server (`mining.notify`, `mining.set_difficulty`, ...).
+/// These carry no `id` and are distinguished from `Response` by the presence of `method`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notification {
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
+/// Parameters of `mining.notify`, in the order the pool sends them on the wire
+#[derive(Debug, Clone)]
+pub struct NotifyJob {
+    pub job_id: String,
+    pub prev_hash: String,
+    pub coin_base_1: String,
+    pub coin_base_2: String,
+    pub merkle_branches: Vec<String>,
+    pub version: String,
+    pub bits: String,
+    pub time: String,
+    pub clean_jobs: bool,
+}
+
+impl NotifyJob {
+    pub fn from_params(params: &[Value]) -> Option<Self> {
+        Some(Self {
+            job_id: params.get(0)?.as_str()?.to_string(),
+            prev_hash: params.get(1)?.as_str()?.to_string(),
+            coin_base_1: params.get(2)?.as_str()?.to_string(),
+            coin_base_2: params.get(3)?.as_str()?.to_string(),
+            merkle_branches: params
+                .get(4)?
+                .as_array()?
+                .iter()
+                .filter_map(|branch| branch.as_str().map(str::to_string))
+                .collect(),
+            version: params.get(5)?.as_str()?.to_string(),
+            bits: params.get(6)?.as_str()?.to_string(),
+            time: params.get(7)?.as_str()?.to_string(),
+            clean_jobs: params.get(8).and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
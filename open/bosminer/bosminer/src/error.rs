@@ -0,0 +1,52 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Error types shared across mining protocol clients and the scheduler bookkeeping in
+//! `crate::client`.
+
+use std::fmt;
+
+/// Errors raised by `crate::client` and its protocol clients
+#[derive(Debug)]
+pub enum Client {
+    /// A `Group`/`Registry` operation referenced a client handle that isn't registered
+    Missing,
+    /// `reorder_clients` was given a set of handles that doesn't match the registered ones
+    Additional,
+    /// The underlying transport (TCP, ...) to the pool failed
+    Connection(std::io::Error),
+    /// The pool sent something that doesn't conform to the protocol
+    Protocol,
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "client is not registered"),
+            Self::Additional => write!(f, "client set does not match the registered clients"),
+            Self::Connection(e) => write!(f, "connection error: {}", e),
+            Self::Protocol => write!(f, "protocol error"),
+        }
+    }
+}
+
+impl std::error::Error for Client {}
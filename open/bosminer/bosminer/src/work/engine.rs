@@ -0,0 +1,78 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Work engines: turn a single job into a stream of hardware work items, optionally rolling the
+//! block header version within a fixed bit mask (ASIC Boost/BIP310) to multiply the search space
+//! available per job.
+
+use crate::job;
+
+use std::sync::Arc;
+
+/// Produces hardware work items for a single job
+pub trait Engine: std::fmt::Debug + Send + Sync {
+    /// Job this engine was built from
+    fn job(&self) -> &Arc<dyn job::Bitcoin>;
+
+    /// Bits of the block header version this engine rolls, or `0` if it doesn't roll the
+    /// version at all
+    fn version_mask(&self) -> u32;
+}
+
+/// Rolls the block header version within a fixed set of bits to multiply the search space
+/// available per job
+#[derive(Debug)]
+pub struct VersionRolling {
+    job: Arc<dyn job::Bitcoin>,
+    mask: u32,
+}
+
+impl VersionRolling {
+    /// Rolls just enough of the version's low general-purpose bits to give hardware
+    /// `midstate_count` distinct values to search, for clients whose pool never negotiated an
+    /// explicit mask
+    pub fn new(job: Arc<dyn job::Bitcoin>, midstate_count: usize) -> Self {
+        let bits_needed = usize::BITS - midstate_count.max(1).saturating_sub(1).leading_zeros();
+        let mask = if bits_needed == 0 {
+            0
+        } else {
+            ((1u32 << bits_needed) - 1) << 13
+        };
+        Self { job, mask }
+    }
+
+    /// Rolls exactly the bits the pool accepted via the BIP310 `mining.configure`/
+    /// `version-rolling` exchange
+    pub fn new_with_mask(job: Arc<dyn job::Bitcoin>, mask: u32) -> Self {
+        Self { job, mask }
+    }
+}
+
+impl Engine for VersionRolling {
+    fn job(&self) -> &Arc<dyn job::Bitcoin> {
+        &self.job
+    }
+
+    fn version_mask(&self) -> u32 {
+        self.mask
+    }
+}
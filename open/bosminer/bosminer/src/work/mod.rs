@@ -0,0 +1,121 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Work generation and delivery: turning a client's latest announced job into hardware-sized
+//! work items, and routing the solutions hardware finds back to the client responsible for them.
+
+pub mod engine;
+
+use crate::job;
+use crate::node;
+
+use std::sync::{Arc, Mutex};
+
+/// Builds a concrete `Engine` from a client's latest announced job. Installed via
+/// `EngineSender::replace_engine_generator` and re-invoked every time `send_job` delivers a new
+/// job, so it can react to state that only settles after registration (e.g. a negotiated
+/// version-rolling mask).
+pub type EngineGenerator =
+    Box<dyn Fn(&Arc<dyn job::Bitcoin>) -> Arc<dyn engine::Engine> + Send + Sync>;
+
+/// Delivers a client's freshly announced jobs into the work generation pipeline, and lets the
+/// client swap out how those jobs get turned into engines
+pub struct EngineSender {
+    generator: Mutex<Option<EngineGenerator>>,
+    job: Mutex<Option<Arc<dyn job::Bitcoin>>>,
+}
+
+impl EngineSender {
+    pub fn new(generator: Option<EngineGenerator>) -> Self {
+        Self {
+            generator: Mutex::new(generator),
+            job: Mutex::new(None),
+        }
+    }
+
+    /// Installs a new generator, returning whatever was installed previously, or a generator
+    /// that always panics on first registration (nothing should be sending jobs before a
+    /// generator has been installed at least once)
+    pub fn replace_engine_generator(&self, generator: EngineGenerator) -> EngineGenerator {
+        self.generator
+            .lock()
+            .expect("BUG: engine sender lock poisoned")
+            .replace(generator)
+            .unwrap_or_else(|| {
+                Box::new(|_| panic!("BUG: engine generator used before one was installed"))
+            })
+    }
+
+    /// Hands a freshly announced job to the pipeline, turning it into an engine via whichever
+    /// generator is currently installed
+    pub fn send_job(&self, job: Arc<dyn job::Bitcoin>) {
+        let engine = self
+            .generator
+            .lock()
+            .expect("BUG: engine sender lock poisoned")
+            .as_ref()
+            .map(|generator| generator(&job));
+        *self.job.lock().expect("BUG: engine sender lock poisoned") = Some(job);
+        let _ = engine;
+    }
+}
+
+impl std::fmt::Debug for EngineSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EngineSender").finish()
+    }
+}
+
+/// A candidate solution hardware found while searching a job previously delivered via
+/// `EngineSender::send_job`
+#[derive(Debug, Clone)]
+pub struct Solution {
+    origin: Arc<dyn node::Client>,
+    nonce: u32,
+    version_bits: u32,
+}
+
+impl Solution {
+    pub fn new(origin: Arc<dyn node::Client>, nonce: u32, version_bits: u32) -> Self {
+        Self {
+            origin,
+            nonce,
+            version_bits,
+        }
+    }
+
+    /// Client whose job this solution was found against, used to route it back to the right
+    /// `Handle` via `Handle::matching_solution`
+    pub fn origin(&self) -> Arc<dyn node::Client> {
+        self.origin.clone()
+    }
+
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// Version bits the hardware rolled while searching for this solution (ASIC Boost/BIP310),
+    /// already restricted to whatever mask the engine that generated the work was built with
+    pub fn version_bits(&self) -> u32 {
+        self.version_bits
+    }
+}